@@ -1,5 +1,7 @@
 use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
 
+use anyhow::anyhow;
 use lsp_server::{ErrorCode, ExtractError, Notification, Request, RequestId};
 use lsp_types::notification::{
     Cancel, DidChangeConfiguration, DidChangeTextDocument, DidChangeWatchedFiles,
@@ -10,11 +12,12 @@ use lsp_types::request::{
     CodeActionRequest, Completion, ExecuteCommand, Formatting, GotoDefinition, HoverRequest,
     Request as RequestTrait, SemanticTokensFullRequest,
 };
+use lsp_types::NumberOrString;
+use salsa::Cancelled;
 use tracing::{error, warn};
 
 use crate::lsp::ext::{ExpandMacro, ProvideVirtualFile, ViewAnalyzedCrates};
 use crate::server::schedule::Task;
-use crate::Backend;
 
 pub mod traits;
 
@@ -23,6 +26,11 @@ use super::schedule::BackgroundSchedule;
 use crate::state::State;
 
 pub(crate) fn request<'a>(request: Request) -> Task<'a> {
+    // Ensure the context-aware panic hook is installed before any handler can
+    // run. Idempotent, so routing cost is a single atomic check after the first
+    // request.
+    panic_context::install_hook();
+
     let id = request.id.clone();
 
     match request.method.as_str() {
@@ -72,7 +80,7 @@ pub(crate) fn request<'a>(request: Request) -> Task<'a> {
 
 pub(crate) fn notification<'a>(notification: Notification) -> Task<'a> {
     match notification.method.as_str() {
-        Cancel::METHOD => local_notification_task::<Cancel>(notification),
+        Cancel::METHOD => cancel_notification_task(notification),
         DidChangeTextDocument::METHOD => {
             local_notification_task::<DidChangeTextDocument>(notification)
         }
@@ -102,30 +110,259 @@ pub(crate) fn notification<'a>(notification: Notification) -> Task<'a> {
 
 fn local_request_task<'a, R: traits::SyncRequestHandler>(
     request: Request,
-) -> Result<Task<'a>, LSPError> {
+) -> Result<Task<'a>, LSPError>
+where
+    R::Params: std::fmt::Debug,
+{
     let (id, params) = cast_request::<R>(request)?;
     Ok(Task::local(move |state, notifier, requester, responder| {
+        let _panic_context = panic_context::enter(panic_frame(R::METHOD, &params));
         let result = R::run(state, notifier, requester, params);
         respond::<R>(id, result, &responder);
     }))
 }
 
+/// Upper bound on how many attempts, the first one included, a
+/// latency-sensitive request gets when content-modified cancellations force it
+/// to be re-run, before we give up and report
+/// [`ContentModified`](ErrorCode::ContentModified) to the client.
+const MAX_BACKGROUND_ATTEMPTS: usize = 3;
+
 fn background_request_task<'a, R: traits::BackgroundDocumentRequestHandler>(
     request: Request,
     schedule: BackgroundSchedule,
-) -> Result<Task<'a>, LSPError> {
+) -> Result<Task<'a>, LSPError>
+where
+    R::Params: Clone + std::fmt::Debug,
+{
     let (id, params) = cast_request::<R>(request)?;
+    // Only latency-sensitive requests are worth retrying: they are issued
+    // densely while the user types, so the first attempt is routinely
+    // invalidated by the next keystroke's edit. Heavier worker requests simply
+    // fail fast and are re-requested by the editor if still needed.
+    let attempts = match schedule {
+        BackgroundSchedule::LatencySensitive => MAX_BACKGROUND_ATTEMPTS,
+        _ => 1,
+    };
     Ok(Task::background(schedule, move |state: &State| {
-        let state_snapshot = state.snapshot();
+        // Track the request for the lifetime of the background job so that an
+        // incoming `Cancel` notification can find it and unwind its snapshot;
+        // the entry is cleared once we reply, whatever the outcome.
+        state.scheduled_requests.lock().insert(id.clone());
+        let scheduled_requests = state.scheduled_requests.clone();
+        let cancelled_requests = state.cancelled_requests.clone();
+        // A cheap handle that lets the background thread take a fresh snapshot
+        // of the latest database state when an earlier attempt is invalidated.
+        let snapshots = state.snapshots();
         Box::new(move |notifier, responder| {
-            let result =
-                Backend::catch_panics(|| R::run_with_snapshot(state_snapshot, notifier, params))
-                    .and_then(|res| res);
+            let result = run_with_retry::<R>(
+                &snapshots,
+                &notifier,
+                params,
+                &id,
+                &cancelled_requests,
+                attempts,
+            );
+            // Clear both registries: `scheduled_requests` so a later `Cancel`
+            // no-ops, and `cancelled_requests` so a `Cancel` that raced in after
+            // the request already finished does not leave a dangling entry.
+            scheduled_requests.lock().remove(&id);
+            cancelled_requests.lock().remove(&id);
             respond::<R>(id, result, &responder);
         })
     }))
 }
 
+/// Runs a background handler, retrying against a fresh snapshot when an attempt
+/// unwinds because a newer edit invalidated the one it read.
+///
+/// A client-requested cancellation is reported immediately as
+/// [`RequestCanceled`](ErrorCode::RequestCanceled). An edit-invalidated one is
+/// run at most `attempts` times in all before falling back to
+/// [`ContentModified`](ErrorCode::ContentModified); the bound keeps sustained
+/// typing from livelocking the request.
+fn run_with_retry<R: traits::BackgroundDocumentRequestHandler>(
+    snapshots: &crate::state::Snapshots,
+    notifier: &super::client::Notifier,
+    params: R::Params,
+    id: &RequestId,
+    cancelled_requests: &parking_lot::Mutex<rustc_hash::FxHashSet<RequestId>>,
+    attempts: usize,
+) -> LSPResult<R::Result>
+where
+    R::Params: Clone + std::fmt::Debug,
+{
+    // Installed once per task so a panic during any attempt reports which
+    // request and params were in flight.
+    let _panic_context = panic_context::enter(panic_frame(R::METHOD, &params));
+    // Always make at least one attempt: a caller passing `0` should get a
+    // regular response, never a panic from falling out of the loop.
+    let attempts = attempts.max(1);
+    for attempt in 1..=attempts {
+        let snapshot = snapshots.take();
+        match catch_panics(|| R::run_with_snapshot(snapshot, notifier.clone(), params.clone())) {
+            Ok(result) => return result,
+            Err(Panic::Cancelled(_)) => {
+                if cancelled_requests.lock().remove(id) {
+                    return Err(LSPError::new(
+                        anyhow!("request {id} was cancelled by the client"),
+                        ErrorCode::RequestCanceled,
+                    ));
+                }
+                // Invalidated by a newer edit: re-run against the freshest
+                // snapshot unless this was our last attempt.
+                if attempt < attempts {
+                    continue;
+                }
+                return Err(LSPError::new(
+                    anyhow!("request {id} was invalidated by a newer edit"),
+                    ErrorCode::ContentModified,
+                ));
+            }
+            Err(Panic::Unwind(message)) => {
+                return Err(LSPError::new(anyhow!("{message}"), ErrorCode::InternalError));
+            }
+        }
+    }
+    // `attempts >= 1` guarantees the loop runs and returns, so this is only
+    // here to satisfy the type checker. Report ContentModified rather than
+    // panicking, so a future refactor can never turn a request into a crash.
+    Err(LSPError::new(
+        anyhow!("request {id} exhausted its retry budget"),
+        ErrorCode::ContentModified,
+    ))
+}
+
+/// Distinguishes a salsa cancellation from any other handler panic.
+enum Panic {
+    /// A salsa query unwound because the database it read was mutated or
+    /// cancellation was requested.
+    Cancelled(Cancelled),
+    /// The handler panicked for some other reason; carries the rendered message.
+    Unwind(String),
+}
+
+/// Runs `f`, catching panics and separating a salsa [`Cancelled`] unwind from a
+/// genuine crash so the two can be reported with different LSP status codes.
+fn catch_panics<T>(f: impl FnOnce() -> T) -> Result<T, Panic> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        // Our panic hook stashes the message enriched with the context stack;
+        // take it regardless of the branch so it does not leak into the next
+        // panic on this thread.
+        let enriched = panic_context::take_last_panic();
+        match payload.downcast::<Cancelled>() {
+            Ok(cancelled) => Panic::Cancelled(*cancelled),
+            Err(payload) => Panic::Unwind(enriched.unwrap_or_else(|| panic_message(&payload))),
+        }
+    })
+}
+
+/// Renders a caught panic payload into a human-readable message.
+fn panic_message(payload: &Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| (*s).to_owned())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "a background request handler panicked".to_owned())
+}
+
+/// Builds a panic-context frame describing the request currently being served,
+/// with a truncated `Debug` of its params so crash reports are actionable
+/// without a reproduction.
+fn panic_frame<P: std::fmt::Debug>(method: &str, params: &P) -> String {
+    const MAX_PARAMS_LEN: usize = 200;
+    let rendered = format!("{params:?}");
+    let rendered = if rendered.chars().count() > MAX_PARAMS_LEN {
+        let truncated: String = rendered.chars().take(MAX_PARAMS_LEN).collect();
+        format!("{truncated}…")
+    } else {
+        rendered
+    };
+    format!(
+        "panicked handling {method} (version {}) params {rendered}",
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// A thread-local stack of human-readable frames describing the work in
+/// progress, appended to panic messages so `catch_panics` can report which
+/// request and input triggered a crash. Analogous to rust-analyzer's
+/// `panic_context`.
+mod panic_context {
+    use std::cell::RefCell;
+    use std::panic;
+    use std::sync::Once;
+
+    thread_local! {
+        static CONTEXT_STACK: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+        /// The most recent panic message enriched with the context stack,
+        /// stashed by our hook for `catch_panics` to consume.
+        static LAST_PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+    }
+
+    /// Pushes `frame` onto the current thread's context stack, popping it when
+    /// the returned guard is dropped.
+    pub(super) fn enter(frame: String) -> PanicContextGuard {
+        CONTEXT_STACK.with(|stack| stack.borrow_mut().push(frame));
+        PanicContextGuard(())
+    }
+
+    pub(super) struct PanicContextGuard(());
+
+    impl Drop for PanicContextGuard {
+        fn drop(&mut self) {
+            CONTEXT_STACK.with(|stack| {
+                stack.borrow_mut().pop();
+            });
+        }
+    }
+
+    /// Installs a panic hook that records the panic message together with the
+    /// current context stack. Idempotent: only the first call installs a hook.
+    pub(crate) fn install_hook() {
+        static HOOK: Once = Once::new();
+        HOOK.call_once(|| {
+            let default_hook = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                let message = CONTEXT_STACK.with(|stack| {
+                    let stack = stack.borrow();
+                    if stack.is_empty() {
+                        info.to_string()
+                    } else {
+                        format!("{info}\n{}", stack.join("\n"))
+                    }
+                });
+                LAST_PANIC.with(|last| *last.borrow_mut() = Some(message));
+                default_hook(info);
+            }));
+        });
+    }
+
+    /// Takes the enriched message recorded by the most recent panic on this
+    /// thread, if any.
+    pub(super) fn take_last_panic() -> Option<String> {
+        LAST_PANIC.with(|last| last.borrow_mut().take())
+    }
+}
+
+/// Handles a client `Cancel` notification: mark the named request as cancelled
+/// and trigger salsa cancellation so the background task blocked on its
+/// snapshot unwinds and replies with
+/// [`RequestCanceled`](ErrorCode::RequestCanceled).
+///
+/// Ids that are not currently in flight — already answered, or never scheduled
+/// as background requests — are ignored.
+fn cancel_notification_task<'a>(notification: Notification) -> Result<Task<'a>, LSPError> {
+    let (_, params) = cast_notification::<Cancel>(notification)?;
+    Ok(Task::local(move |state: &mut State, _notifier, _requester, _responder| {
+        let id = match params.id {
+            NumberOrString::Number(id) => RequestId::from(id),
+            NumberOrString::String(id) => RequestId::from(id),
+        };
+        state.cancel(id);
+    }))
+}
+
 fn local_notification_task<'a, N: traits::SyncNotificationHandler>(
     notification: Notification,
 ) -> Result<Task<'a>, LSPError> {
@@ -232,3 +469,28 @@ impl fmt::Display for LSPError {
         self.error.fmt(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::panic_frame;
+
+    #[test]
+    fn panic_frame_keeps_short_params_verbatim() {
+        let frame = panic_frame("textDocument/hover", &"hi");
+        assert!(frame.starts_with("panicked handling textDocument/hover (version "));
+        assert!(frame.ends_with("params \"hi\""));
+        assert!(!frame.contains('…'));
+    }
+
+    #[test]
+    fn panic_frame_truncates_long_params_with_marker() {
+        // Debug-renders to 300 `x`s wrapped in quotes, comfortably over the cap.
+        let params = "x".repeat(300);
+        let frame = panic_frame("textDocument/completion", &params);
+
+        let rendered = frame.split("params ").last().unwrap();
+        assert!(rendered.ends_with('…'), "truncated params should end with the marker");
+        // 200 retained characters plus the single `…` marker.
+        assert_eq!(rendered.chars().count(), 201);
+    }
+}
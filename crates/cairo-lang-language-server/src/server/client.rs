@@ -1,7 +1,11 @@
 use std::any::TypeId;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use lsp_server::{Notification, RequestId};
+use lsp_server::{ErrorCode, Notification, RequestId};
+use lsp_types::notification::Notification as NotificationTrait;
 use rustc_hash::FxHashMap;
 use serde_json::Value;
 
@@ -11,6 +15,28 @@ use crate::server::connection::ClientSender;
 
 type ResponseBuilder<'s> = Box<dyn FnOnce(lsp_server::Response) -> Task<'s>>;
 
+/// How long a server-initiated request may stay unanswered before its handler
+/// is swept away. Mirrors RLS's `DEFAULT_REQUEST_TIMEOUT`.
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A response handler awaiting its reply from the client, together with the
+/// bookkeeping needed to reclaim it if the client never answers.
+struct PendingRequest<'s> {
+    /// LSP method the request was sent for; used only for logging.
+    method: &'static str,
+    /// When the request was dispatched, for timeout accounting.
+    sent_at: Instant,
+    /// How long to wait before considering the request abandoned.
+    timeout: Duration,
+    /// Dispatched when the response finally arrives.
+    handler: ResponseBuilder<'s>,
+    /// Optional task dispatched instead of the synthetic-error handler drive
+    /// when the request is swept away for timing out, letting the caller run a
+    /// recovery action (a default value, a re-request) in place of the answer
+    /// that never came.
+    fallback: Option<Task<'s>>,
+}
+
 pub struct Client<'s> {
     notifier: Notifier,
     responder: Responder,
@@ -26,7 +52,7 @@ pub struct Responder(ClientSender);
 pub struct Requester<'s> {
     sender: ClientSender,
     next_request_id: i32,
-    response_handlers: FxHashMap<RequestId, ResponseBuilder<'s>>,
+    response_handlers: FxHashMap<RequestId, PendingRequest<'s>>,
 }
 
 impl<'s> Client<'s> {
@@ -93,49 +119,81 @@ impl<'s> Requester<'s> {
     where
         R: lsp_types::request::Request,
     {
-        let serialized_params = serde_json::to_value(params)?;
+        self.request_with_timeout::<R>(params, DEFAULT_REQUEST_TIMEOUT, None, response_handler)
+    }
 
-        self.response_handlers.insert(
-            self.next_request_id.into(),
+    /// Like [`Requester::request`], but with an explicit timeout after which
+    /// [`Requester::sweep_timeouts`] reclaims the handler if the client has not
+    /// answered.
+    ///
+    /// When `fallback` is supplied it is dispatched in place of the handler once
+    /// the request is swept away for timing out, giving the caller a hook to run
+    /// a recovery action instead of silently dropping the request.
+    pub fn request_with_timeout<R>(
+        &mut self,
+        params: R::Params,
+        timeout: Duration,
+        fallback: Option<Task<'s>>,
+        response_handler: impl Fn(R::Result) -> Task<'s> + 'static,
+    ) -> Result<()>
+    where
+        R: lsp_types::request::Request,
+    {
+        let handler: ResponseBuilder<'s> =
             Box::new(move |response: lsp_server::Response| {
-                match (response.error, response.result) {
-                    (Some(err), _) => {
-                        tracing::error!(
-                            "Got an error from the client (code {}): {}",
-                            err.code,
-                            err.message
-                        );
-                        Task::nothing()
-                    }
-                    (None, Some(response)) => match serde_json::from_value(response) {
-                        Ok(response) => response_handler(response),
-                        Err(error) => {
-                            tracing::error!("Failed to deserialize response from server: {error}");
-                            Task::nothing()
-                        }
-                    },
-                    (None, None) => {
-                        if TypeId::of::<R::Result>() == TypeId::of::<()>() {
-                            // We can't call `response_handler(())` directly here, but
-                            // since we _know_ the type expected is `()`, we can use
-                            // `from_value(Value::Null)`. `R::Result` implements `DeserializeOwned`,
-                            // so this branch works in the general case but we'll only
-                            // hit it if the concrete type is `()`, so the `unwrap()` is safe here.
-                            response_handler(serde_json::from_value(Value::Null).unwrap());
-                        } else {
-                            tracing::error!(
-                                "Server response was invalid: did not contain a result or error"
-                            );
-                        }
+                match decode_response::<R>(response) {
+                    Ok(result) => response_handler(result),
+                    Err((id, message)) => {
+                        tracing::error!("request {id} failed: {message}");
                         Task::nothing()
                     }
                 }
-            }),
+            });
+
+        self.send::<R>(params, timeout, fallback, handler)
+    }
+
+    /// Sends one request of kind `R` to the client and registers `handler` to
+    /// be run against the raw response. Shared by the typed and batched request
+    /// entry points.
+    fn send<R>(
+        &mut self,
+        params: R::Params,
+        timeout: Duration,
+        fallback: Option<Task<'s>>,
+        handler: ResponseBuilder<'s>,
+    ) -> Result<()>
+    where
+        R: lsp_types::request::Request,
+    {
+        let serialized_params = serde_json::to_value(params)?;
+        self.send_serialized(R::METHOD, serialized_params, timeout, fallback, handler)
+    }
+
+    /// Registers `handler` and sends an already-serialized request, so batched
+    /// callers can validate every item before any of them is dispatched.
+    fn send_serialized(
+        &mut self,
+        method: &'static str,
+        serialized_params: Value,
+        timeout: Duration,
+        fallback: Option<Task<'s>>,
+        handler: ResponseBuilder<'s>,
+    ) -> Result<()> {
+        self.response_handlers.insert(
+            self.next_request_id.into(),
+            PendingRequest {
+                method,
+                sent_at: Instant::now(),
+                timeout,
+                handler,
+                fallback,
+            },
         );
 
         self.sender.send(lsp_server::Message::Request(lsp_server::Request {
             id: self.next_request_id.into(),
-            method: R::METHOD.into(),
+            method: method.into(),
             params: serialized_params,
         }))?;
 
@@ -144,12 +202,341 @@ impl<'s> Requester<'s> {
         Ok(())
     }
 
+    /// Sends several requests of kind `R` but defers `response_handler` until
+    /// every item has been answered, so callers that correlate results — e.g. a
+    /// multi-section `workspace/configuration` refresh — act once rather than
+    /// hand-rolling counters. Failures are reported by input index alongside the
+    /// successful results.
+    pub fn request_batch<R>(
+        &mut self,
+        params: Vec<R::Params>,
+        response_handler: impl FnOnce(BatchResponse<R::Result>) -> Task<'s> + 'static,
+    ) -> Result<()>
+    where
+        R: lsp_types::request::Request,
+    {
+        if params.is_empty() {
+            tracing::debug!("request_batch called with no items; nothing to send");
+            return Ok(());
+        }
+
+        // Serialize every item before sending any, so the batch is all-or-nothing:
+        // a serialization error leaves `response_handlers` untouched and nothing
+        // is left half-registered when we return `Err`.
+        let serialized: Vec<Value> =
+            params.iter().map(serde_json::to_value).collect::<Result<_, _>>()?;
+
+        let count = serialized.len();
+        let batch = Rc::new(RefCell::new(Batch {
+            remaining: count,
+            responses: (0..count).map(|_| None).collect(),
+            failures: Vec::new(),
+            handler: Some(Box::new(response_handler)),
+        }));
+
+        for (index, item) in serialized.into_iter().enumerate() {
+            let batch = batch.clone();
+            let handler: ResponseBuilder<'s> = Box::new(move |response: lsp_server::Response| {
+                let mut batch = batch.borrow_mut();
+                match decode_response::<R>(response) {
+                    Ok(result) => batch.responses[index] = Some(result),
+                    Err((_, message)) => batch.failures.push((index, message)),
+                }
+                batch.remaining -= 1;
+                if batch.remaining == 0 {
+                    let handler = batch.handler.take().expect("handler is dispatched exactly once");
+                    let responses = batch.responses.drain(..).flatten().collect();
+                    let failures = std::mem::take(&mut batch.failures);
+                    drop(batch);
+                    return handler(BatchResponse { responses, failures });
+                }
+                Task::nothing()
+            });
+            self.send_serialized(R::METHOD, item, DEFAULT_REQUEST_TIMEOUT, None, handler)?;
+        }
+
+        Ok(())
+    }
+
     pub fn pop_response_task(&mut self, response: lsp_server::Response) -> Task<'s> {
-        if let Some(handler) = self.response_handlers.remove(&response.id) {
-            handler(response)
+        if let Some(pending) = self.response_handlers.remove(&response.id) {
+            (pending.handler)(response)
         } else {
             tracing::error!("Received a response with ID {}, which was not expected", response.id);
             Task::nothing()
         }
     }
-}
\ No newline at end of file
+
+    /// Reclaims the handlers of requests the client never answered within their
+    /// timeout, returning any [`Task`]s their completion produced.
+    ///
+    /// Driven from the event loop's periodic maintenance tick — not from
+    /// response arrival — so a request that is abandoned while no other traffic
+    /// is flowing (e.g. a `workspace/configuration` the client ignores) is
+    /// still reclaimed. For every abandoned request we log the timed-out method
+    /// and id and send the client a `$/cancelRequest` so both sides agree the
+    /// request is no longer pending.
+    ///
+    /// A request that registered a fallback [`Task`] has that task dispatched in
+    /// its place, letting the caller recover explicitly. Otherwise the handler
+    /// is driven with a synthetic timeout error rather than merely dropped, so
+    /// completion accounting still runs: a [`request_batch`] item decrements its
+    /// counter and records a partial failure instead of wedging the whole batch,
+    /// and a plain request logs its failure. Either way the resulting task —
+    /// the fallback, or a now-complete batch's aggregate handler — is returned
+    /// for the caller to dispatch.
+    ///
+    /// [`request_batch`]: Requester::request_batch
+    pub fn sweep_timeouts(&mut self) -> Vec<Task<'s>> {
+        let now = Instant::now();
+        let expired: Vec<RequestId> = self
+            .response_handlers
+            .iter()
+            .filter(|(_, pending)| now.duration_since(pending.sent_at) >= pending.timeout)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        let mut tasks = Vec::with_capacity(expired.len());
+        for id in expired {
+            // `id` was just collected from the map, so the entry is present.
+            let pending = self.response_handlers.remove(&id).unwrap();
+            tracing::warn!(
+                "request {id} ({}) timed out after {:?}",
+                pending.method,
+                pending.timeout
+            );
+            if let Err(err) = self.cancel(id.clone()) {
+                tracing::error!("failed to notify client of abandoned request: {err}");
+            }
+            if let Some(fallback) = pending.fallback {
+                tasks.push(fallback);
+            } else {
+                let response = lsp_server::Response::new_err(
+                    id,
+                    ErrorCode::RequestCanceled as i32,
+                    "request timed out".to_owned(),
+                );
+                tasks.push((pending.handler)(response));
+            }
+        }
+        tasks
+    }
+
+    /// Sends the client a `$/cancelRequest` for a request we are no longer
+    /// waiting on.
+    fn cancel(&self, id: RequestId) -> Result<()> {
+        let params = lsp_types::CancelParams { id: cancel_id(&id) };
+        self.sender.send(lsp_server::Message::Notification(Notification::new(
+            lsp_types::notification::Cancel::METHOD.to_string(),
+            params,
+        )))
+    }
+}
+
+/// Converts an [`lsp_server::RequestId`] into the [`lsp_types::NumberOrString`]
+/// carried by `$/cancelRequest`.
+fn cancel_id(id: &RequestId) -> lsp_types::NumberOrString {
+    match serde_json::to_value(id) {
+        Ok(Value::Number(n)) if n.is_i64() => {
+            lsp_types::NumberOrString::Number(n.as_i64().unwrap() as i32)
+        }
+        Ok(Value::String(s)) => lsp_types::NumberOrString::String(s),
+        _ => lsp_types::NumberOrString::String(id.to_string()),
+    }
+}
+
+/// Decodes a raw client response into `R::Result`, turning a protocol error or
+/// a malformed payload into a `(RequestId, message)` failure.
+fn decode_response<R>(response: lsp_server::Response) -> Result<R::Result, (RequestId, String)>
+where
+    R: lsp_types::request::Request,
+{
+    let id = response.id.clone();
+    match (response.error, response.result) {
+        (Some(err), _) => {
+            Err((id, format!("client returned an error (code {}): {}", err.code, err.message)))
+        }
+        (None, Some(value)) => serde_json::from_value(value)
+            .map_err(|error| (id, format!("failed to deserialize response: {error}"))),
+        (None, None) => {
+            if TypeId::of::<R::Result>() == TypeId::of::<()>() {
+                // The expected result is `()`, so deserialize from null.
+                // `R::Result` is `DeserializeOwned`, so this only succeeds for
+                // the unit type and the `unwrap()` is safe here.
+                Ok(serde_json::from_value(Value::Null).unwrap())
+            } else {
+                Err((id, "response did not contain a result or error".to_owned()))
+            }
+        }
+    }
+}
+
+/// Accumulator backing [`Requester::request_batch`], counted down per response.
+struct Batch<'s, Res> {
+    /// Outstanding responses; the handler runs when this hits zero.
+    remaining: usize,
+    /// Successful responses slotted by input index; `None` until answered.
+    responses: Vec<Option<Res>>,
+    /// Failures as `(input index, message)`.
+    failures: Vec<(usize, String)>,
+    /// Dispatched once, when the last response arrives.
+    handler: Option<Box<dyn FnOnce(BatchResponse<Res>) -> Task<'s>>>,
+}
+
+/// Aggregated outcome of a [`Requester::request_batch`] call.
+pub struct BatchResponse<Res> {
+    /// Successful responses, in input order (failed items omitted).
+    pub responses: Vec<Res>,
+    /// Failures as `(input index, message)`.
+    pub failures: Vec<(usize, String)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crossbeam::channel::{unbounded, Receiver};
+    use lsp_server::{Message, Response, ResponseError};
+
+    use super::*;
+    use crate::server::connection::ClientSender;
+
+    /// A request whose result is the unit type, exercising the null-for-`()`
+    /// branch of [`decode_response`].
+    enum UnitRequest {}
+
+    impl lsp_types::request::Request for UnitRequest {
+        type Params = ();
+        type Result = ();
+        const METHOD: &'static str = "test/unit";
+    }
+
+    /// A request with a non-unit result, so a missing or malformed payload is an
+    /// error rather than a legal empty response.
+    enum NumberRequest {}
+
+    impl lsp_types::request::Request for NumberRequest {
+        type Params = ();
+        type Result = i64;
+        const METHOD: &'static str = "test/number";
+    }
+
+    fn requester<'s>() -> (Requester<'s>, Receiver<Message>) {
+        let (sender, receiver) = unbounded();
+        let requester = Requester {
+            sender: ClientSender::new(sender),
+            next_request_id: 1,
+            response_handlers: FxHashMap::default(),
+        };
+        (requester, receiver)
+    }
+
+    fn ok_response(id: i32, value: Value) -> Response {
+        Response { id: id.into(), result: Some(value), error: None }
+    }
+
+    fn err_response(id: i32) -> Response {
+        Response {
+            id: id.into(),
+            result: None,
+            error: Some(ResponseError { code: -32603, message: "boom".to_owned(), data: None }),
+        }
+    }
+
+    #[test]
+    fn decode_response_surfaces_client_errors() {
+        let (id, message) = decode_response::<NumberRequest>(err_response(1)).unwrap_err();
+        assert_eq!(id, RequestId::from(1));
+        assert!(message.contains("client returned an error (code -32603)"), "{message}");
+    }
+
+    #[test]
+    fn decode_response_reads_null_as_unit() {
+        let result = decode_response::<UnitRequest>(Response {
+            id: 1.into(),
+            result: None,
+            error: None,
+        });
+        assert_eq!(result.unwrap(), ());
+    }
+
+    #[test]
+    fn decode_response_reports_deserialize_failures() {
+        let (_, message) =
+            decode_response::<NumberRequest>(ok_response(1, Value::from("not a number")))
+                .unwrap_err();
+        assert!(message.contains("failed to deserialize response"), "{message}");
+    }
+
+    #[test]
+    fn cancel_id_preserves_numeric_and_string_ids() {
+        assert!(matches!(
+            cancel_id(&RequestId::from(7)),
+            lsp_types::NumberOrString::Number(7)
+        ));
+        assert!(matches!(
+            cancel_id(&RequestId::from("abc".to_owned())),
+            lsp_types::NumberOrString::String(s) if s == "abc"
+        ));
+    }
+
+    #[test]
+    fn sweep_timeouts_reclaims_expired_handlers() {
+        let (mut requester, _receiver) = requester();
+        // Backdate the handler well past its timeout so the sweep picks it up.
+        requester.response_handlers.insert(
+            1.into(),
+            PendingRequest {
+                method: "test/number",
+                sent_at: Instant::now().checked_sub(Duration::from_secs(120)).unwrap(),
+                timeout: DEFAULT_REQUEST_TIMEOUT,
+                handler: Box::new(|_| Task::nothing()),
+                fallback: None,
+            },
+        );
+        // A fresh handler must survive the sweep untouched.
+        requester.response_handlers.insert(
+            2.into(),
+            PendingRequest {
+                method: "test/number",
+                sent_at: Instant::now(),
+                timeout: DEFAULT_REQUEST_TIMEOUT,
+                handler: Box::new(|_| Task::nothing()),
+                fallback: None,
+            },
+        );
+
+        let tasks = requester.sweep_timeouts();
+
+        assert_eq!(tasks.len(), 1, "only the expired handler should be reclaimed");
+        assert!(!requester.response_handlers.contains_key(&1.into()));
+        assert!(requester.response_handlers.contains_key(&2.into()));
+    }
+
+    #[test]
+    fn request_batch_preserves_order_and_collects_failures() {
+        let (mut requester, _receiver) = requester();
+        let captured: Rc<RefCell<Option<BatchResponse<i64>>>> = Rc::new(RefCell::new(None));
+        let sink = captured.clone();
+
+        requester
+            .request_batch::<NumberRequest>(vec![(), (), ()], move |batch| {
+                *sink.borrow_mut() = Some(batch);
+                Task::nothing()
+            })
+            .unwrap();
+
+        // Answer out of order, with the first item failing, to prove responses
+        // are slotted by request order rather than arrival order.
+        let _ = requester.pop_response_task(ok_response(2, Value::from(20)));
+        let _ = requester.pop_response_task(err_response(1));
+        let _ = requester.pop_response_task(ok_response(3, Value::from(30)));
+
+        let batch = captured.borrow_mut().take().expect("handler runs once all answers arrive");
+        assert_eq!(batch.responses, vec![20, 30]);
+        assert_eq!(batch.failures.len(), 1);
+        // The failing item was the first input, so it is keyed by index 0.
+        assert_eq!(batch.failures[0].0, 0);
+    }
+}
@@ -0,0 +1,72 @@
+//! The language server's event loop and its routing of client messages.
+
+pub(crate) mod api;
+mod client;
+mod connection;
+mod schedule;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use crossbeam::channel::{select, tick};
+use lsp_server::Message;
+
+use self::client::Client;
+use self::connection::Connection;
+use self::schedule::Scheduler;
+use crate::state::State;
+
+/// How often the event loop runs periodic maintenance — reclaiming
+/// server-to-client requests the client never answered — regardless of whether
+/// any other traffic is flowing.
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Owns the long-lived [`State`] and the [`Connection`] to the client and
+/// drives the main event loop.
+pub struct Server {
+    connection: Connection,
+    state: State,
+}
+
+impl Server {
+    pub fn new(connection: Connection, state: State) -> Self {
+        Self { connection, state }
+    }
+
+    /// Runs the event loop until the client closes the connection.
+    pub fn run(self) -> Result<()> {
+        let Server { connection, state } = self;
+        let mut scheduler = Scheduler::new(state, Client::new(connection.sender()));
+
+        let incoming = connection.receiver();
+        // A steady tick that fires even when the client is silent, so abandoned
+        // requests are swept on time rather than only when the next message
+        // happens to arrive.
+        let maintenance = tick(MAINTENANCE_INTERVAL);
+
+        loop {
+            select! {
+                recv(incoming) -> message => {
+                    let Ok(message) = message else { break };
+                    match message {
+                        Message::Request(request) => scheduler.dispatch(api::request(request)),
+                        Message::Notification(notification) => {
+                            scheduler.dispatch(api::notification(notification))
+                        }
+                        Message::Response(response) => {
+                            let task = scheduler.client_mut().requester.pop_response_task(response);
+                            scheduler.dispatch(task);
+                        }
+                    }
+                }
+                recv(maintenance) -> _ => {
+                    for task in scheduler.client_mut().requester.sweep_timeouts() {
+                        scheduler.dispatch(task);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use lsp_server::RequestId;
+use parking_lot::Mutex;
+use rustc_hash::FxHashSet;
+use salsa::{Durability, ParallelDatabase, Snapshot};
+
+use crate::lang::db::AnalysisDatabase;
+
+/// Long-lived server state owned by the main event loop.
+pub struct State {
+    pub db: AnalysisDatabase,
+    /// Ids of background requests currently scheduled or running, so that an
+    /// incoming `Cancel` notification can tell whether a request it names is
+    /// still worth cancelling.
+    pub scheduled_requests: Arc<Mutex<FxHashSet<RequestId>>>,
+    /// Ids the client explicitly asked us to cancel, awaiting the unwind of the
+    /// salsa query they are blocked on. Drained by the request task when it
+    /// maps the cancellation onto an LSP status code.
+    pub cancelled_requests: Arc<Mutex<FxHashSet<RequestId>>>,
+}
+
+impl State {
+    pub fn new(db: AnalysisDatabase) -> Self {
+        Self {
+            db,
+            scheduled_requests: Arc::default(),
+            cancelled_requests: Arc::default(),
+        }
+    }
+
+    /// Takes a read-only snapshot of the database for a single background
+    /// request.
+    pub fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot { db: self.db.snapshot() }
+    }
+
+    /// Returns a handle for taking further snapshots from a background thread, so
+    /// a request invalidated by a newer edit can be re-run against fresh data.
+    pub fn snapshots(&self) -> Snapshots {
+        Snapshots { db: self.db.snapshot() }
+    }
+
+    /// Marks `id` as cancelled by the client and bumps the salsa revision so the
+    /// blocked request unwinds with a [`salsa::Cancelled`] payload. No-ops for
+    /// ids that are not currently in flight.
+    pub fn cancel(&mut self, id: RequestId) {
+        if !self.scheduled_requests.lock().contains(&id) {
+            return;
+        }
+        self.cancelled_requests.lock().insert(id);
+        // A synthetic write bumps the revision, which cancels every outstanding
+        // snapshot and makes the cancelled request unwind promptly.
+        self.db.salsa_runtime_mut().synthetic_write(Durability::LOW);
+    }
+}
+
+/// A read-only snapshot of [`State`] handed to a background request handler.
+pub struct StateSnapshot {
+    pub db: Snapshot<AnalysisDatabase>,
+}
+
+/// A handle onto the database for taking a fresh [`StateSnapshot`] after an
+/// attempt is invalidated by a document edit.
+pub struct Snapshots {
+    db: Snapshot<AnalysisDatabase>,
+}
+
+impl Snapshots {
+    /// Takes a fresh snapshot for the next request attempt.
+    pub fn take(&self) -> StateSnapshot {
+        StateSnapshot { db: self.db.snapshot() }
+    }
+}